@@ -0,0 +1,36 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Entry points for running sessions remotely against the Bonsai proving
+//! service. Bonsai support is not available in this build: every function
+//! here returns an error.
+
+use anyhow::Result;
+
+/// Register a proof with Bonsai. Always returns an error in this build; no
+/// proof ID for [run_proof] is ever produced.
+pub fn register_proof(
+    _elf: &[u8],
+    _bonsai_url: String,
+    _image_id: [u32; 8],
+    _input: Vec<u8>,
+) -> Result<i64> {
+    anyhow::bail!("Bonsai support is not available in this build")
+}
+
+/// Run a previously-registered proof. Always returns an error in this build;
+/// no receipt is returned.
+pub fn run_proof(_bonsai_url: String, _proof_id: i64) -> Result<()> {
+    anyhow::bail!("Bonsai support is not available in this build")
+}
@@ -0,0 +1,88 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Loading a RISC-V ELF binary into a flat guest memory layout.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+/// Number of cycles the proving system spends setting up a segment before
+/// any guest instruction runs.
+const INIT_CYCLES: usize = 64;
+/// Number of cycles the proving system spends tearing down a segment after
+/// the last guest instruction runs.
+const FINI_CYCLES: usize = 64;
+
+/// A RISC-V program loaded from an ELF binary, as a sparse word image plus
+/// its entry point.
+pub struct Program {
+    /// Word-addressed image of the program's loadable segments.
+    pub(crate) image: BTreeMap<u32, u32>,
+    /// The address `pc` should start at.
+    pub entry: u32,
+}
+
+impl Program {
+    /// Parse `elf` and lay it out within a `max_mem`-byte address space.
+    pub fn load_elf(elf: &[u8], max_mem: u32) -> Result<Self> {
+        let binary = elf::ElfBytes::<elf::endian::LittleEndian>::minimal_parse(elf)?;
+        let mut image = BTreeMap::new();
+        if let Some(segments) = binary.segments() {
+            for segment in segments.iter().filter(|s| s.p_type == elf::abi::PT_LOAD) {
+                let data = binary.segment_data(&segment)?;
+                for (i, chunk) in data.chunks(4).enumerate() {
+                    let addr = segment.p_vaddr as u32 + (i * 4) as u32;
+                    if addr >= max_mem {
+                        continue;
+                    }
+                    let mut word_bytes = [0u8; 4];
+                    word_bytes[..chunk.len()].copy_from_slice(chunk);
+                    image.insert(addr, u32::from_le_bytes(word_bytes));
+                }
+            }
+        }
+        Ok(Self {
+            image,
+            entry: binary.ehdr.e_entry as u32,
+        })
+    }
+}
+
+/// Accounts for the fixed per-segment cycle overhead of loading and
+/// finalizing a segment, independent of guest instructions executed.
+pub struct Loader;
+
+impl Loader {
+    /// Construct a [Loader] for the current proving configuration.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The number of cycles spent initializing a segment.
+    pub fn init_cycles(&self) -> usize {
+        INIT_CYCLES
+    }
+
+    /// The number of cycles spent finalizing a segment.
+    pub fn fini_cycles(&self) -> usize {
+        FINI_CYCLES
+    }
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Configuration for the [super::Executor], built with [ExecutorEnvBuilder].
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+#[cfg(feature = "gdb")]
+use std::net::SocketAddr;
+
+use anyhow::Result;
+
+use super::{io::SimpleIo, monitor::MemoryMonitor, TraceEvent};
+
+/// A host-side handler for a guest `ecall::SOFTWARE` syscall.
+pub trait Syscall {
+    /// Handle a syscall, returning the values to place in `a0`/`a1`.
+    fn syscall(
+        &mut self,
+        syscall_name: &str,
+        monitor: &mut MemoryMonitor,
+        to_guest: &mut [u32],
+    ) -> Result<(u32, u32)>;
+}
+
+/// The default segment limit, specified in powers of 2 cycles.
+const DEFAULT_SEGMENT_LIMIT_PO2: usize = 20; // 1M cycles
+
+/// The default session limit, specified in number of cycles.
+const DEFAULT_SESSION_LIMIT: usize = 64 * 1024 * 1024; // 64M cycles
+
+pub(crate) type TraceCallback<'a> = Rc<RefCell<dyn FnMut(TraceEvent) -> Result<()> + 'a>>;
+
+/// What a [ExecutorEnvBuilder::cycle_callback] asks the [super::Executor] to
+/// do next.
+pub enum Interrupt {
+    /// Keep running.
+    Continue,
+    /// Stop cleanly, as if the guest had called `ecall::HALT` with
+    /// `halt::PAUSE`; the session can be resumed later.
+    Stop,
+}
+
+struct CycleCallback<'a> {
+    interval: usize,
+    callback: Rc<RefCell<dyn FnMut() -> Interrupt + 'a>>,
+}
+
+/// A configuration for a [super::Executor] run, built with [ExecutorEnvBuilder].
+pub struct ExecutorEnv<'a> {
+    pub(crate) input: Vec<u8>,
+    pub(crate) io: RefCell<SimpleIo>,
+    pub(crate) trace_callback: Option<TraceCallback<'a>>,
+    pub(crate) segment_limit_po2: usize,
+    session_limit: usize,
+    syscalls: HashMap<String, Rc<RefCell<dyn Syscall + 'a>>>,
+    trap_vector: Option<u32>,
+    cycle_callback: Option<CycleCallback<'a>>,
+    #[cfg(feature = "gdb")]
+    pub(crate) gdb_listen_addr: Option<SocketAddr>,
+}
+
+impl<'a> ExecutorEnv<'a> {
+    /// Construct a [ExecutorEnvBuilder].
+    pub fn builder() -> ExecutorEnvBuilder<'a> {
+        ExecutorEnvBuilder::default()
+    }
+
+    pub(crate) fn get_session_limit(&self) -> usize {
+        self.session_limit
+    }
+
+    pub(crate) fn get_segment_limit(&self) -> usize {
+        1 << self.segment_limit_po2
+    }
+
+    pub(crate) fn get_syscall(&self, name: &str) -> Option<Rc<RefCell<dyn Syscall + 'a>>> {
+        self.syscalls.get(name).cloned()
+    }
+
+    #[cfg(feature = "gdb")]
+    pub(crate) fn gdb_listen_addr(&self) -> Option<SocketAddr> {
+        self.gdb_listen_addr
+    }
+
+    pub(crate) fn trap_vector(&self) -> Option<u32> {
+        self.trap_vector
+    }
+
+    /// The configured cycle interval of the [ExecutorEnvBuilder::cycle_callback],
+    /// if any was registered.
+    pub(crate) fn cycle_interrupt_interval(&self) -> Option<usize> {
+        self.cycle_callback.as_ref().map(|cb| cb.interval)
+    }
+
+    /// Invoke the registered [ExecutorEnvBuilder::cycle_callback]. Panics if
+    /// none was registered; callers should guard with
+    /// [Self::cycle_interrupt_interval].
+    pub(crate) fn fire_cycle_interrupt(&self) -> Interrupt {
+        (self.cycle_callback.as_ref().unwrap().callback.borrow_mut())()
+    }
+}
+
+/// Builder for constructing an [ExecutorEnv].
+pub struct ExecutorEnvBuilder<'a> {
+    inner: ExecutorEnv<'a>,
+}
+
+impl<'a> Default for ExecutorEnvBuilder<'a> {
+    fn default() -> Self {
+        Self {
+            inner: ExecutorEnv {
+                input: Vec::new(),
+                io: RefCell::new(SimpleIo::default()),
+                trace_callback: None,
+                segment_limit_po2: DEFAULT_SEGMENT_LIMIT_PO2,
+                session_limit: DEFAULT_SESSION_LIMIT,
+                syscalls: HashMap::new(),
+                trap_vector: None,
+                cycle_callback: None,
+                #[cfg(feature = "gdb")]
+                gdb_listen_addr: None,
+            },
+        }
+    }
+}
+
+impl<'a> ExecutorEnvBuilder<'a> {
+    /// Finalize this builder, returning an [ExecutorEnv].
+    pub fn build(&mut self) -> ExecutorEnv<'a> {
+        std::mem::take(&mut self.inner)
+    }
+
+    /// Provide the guest with the given input bytes.
+    pub fn add_input(&mut self, input: &[u8]) -> &mut Self {
+        self.inner.input.extend_from_slice(input);
+        self
+    }
+
+    /// Set the segment cycle limit, specified in powers of 2 cycles.
+    pub fn segment_limit_po2(&mut self, limit: usize) -> &mut Self {
+        self.inner.segment_limit_po2 = limit;
+        self
+    }
+
+    /// Set the overall session cycle limit.
+    pub fn session_limit(&mut self, limit: usize) -> &mut Self {
+        self.inner.session_limit = limit;
+        self
+    }
+
+    /// Register a callback to be invoked on every [TraceEvent].
+    pub fn trace_callback(
+        &mut self,
+        callback: impl FnMut(TraceEvent) -> Result<()> + 'a,
+    ) -> &mut Self {
+        self.inner.trace_callback = Some(Rc::new(RefCell::new(callback)));
+        self
+    }
+
+    /// Register a guest trap vector: on a fault, the executor saves the
+    /// faulting pc, sets a cause register, and redirects `pc` here instead
+    /// of terminating the session with [crate::ExitCode::Fault].
+    pub fn trap_handler(&mut self, pc: u32) -> &mut Self {
+        self.inner.trap_vector = Some(pc);
+        self
+    }
+
+    /// Register a periodic callback, modeled on a hardware timer: the
+    /// executor invokes it once every `interval` cycles (measured against
+    /// [super::Executor::session_cycle]), letting host code implement
+    /// watchdogs, progress reporting, or cooperative cancellation of a
+    /// long-running execution without waiting for `SessionLimit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is `0`, since the executor would never advance
+    /// past the next interrupt boundary and the session would hang.
+    pub fn cycle_callback(
+        &mut self,
+        interval: usize,
+        callback: impl FnMut() -> Interrupt + 'a,
+    ) -> &mut Self {
+        assert!(interval > 0, "cycle_callback interval must be non-zero");
+        self.inner.cycle_callback = Some(CycleCallback {
+            interval,
+            callback: Rc::new(RefCell::new(callback)),
+        });
+        self
+    }
+
+    /// Register a handler for a host syscall, invoked by `ecall::SOFTWARE`.
+    pub fn syscall(&mut self, name: &str, handler: impl Syscall + 'a) -> &mut Self {
+        self.inner
+            .syscalls
+            .insert(name.to_string(), Rc::new(RefCell::new(handler)));
+        self
+    }
+
+    /// Serve the running [super::Executor] over the GDB Remote Serial
+    /// Protocol on `addr` when it is driven with
+    /// [super::Executor::run_gdb], which blocks until a debugger connects
+    /// and detaches. [super::Executor::run] does not use this address.
+    #[cfg(feature = "gdb")]
+    pub fn gdb(&mut self, addr: SocketAddr) -> &mut Self {
+        self.inner.gdb_listen_addr = Some(addr);
+        self
+    }
+}
+
+impl<'a> Default for ExecutorEnv<'a> {
+    fn default() -> Self {
+        ExecutorEnvBuilder::default().build()
+    }
+}
+
+impl<'a> std::ops::Deref for ExecutorEnvBuilder<'a> {
+    type Target = ExecutorEnv<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A minimal GDB Remote Serial Protocol stub, driven by [Executor::step].
+//!
+//! This lets a real `gdb` (or `gdb-multiarch`) attach to a running guest over
+//! `target remote`, set breakpoints, single-step, and inspect registers and
+//! memory, without the host program doing anything beyond calling
+//! [GdbStub::serve].
+
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use anyhow::{anyhow, Result};
+
+use super::Executor;
+use crate::ExitCode;
+
+/// Number of RISC-V general-purpose registers GDB expects, plus `pc`.
+const NUM_GDB_REGS: usize = 33;
+
+/// A GDB Remote Serial Protocol server that drives an [Executor] on behalf of
+/// an attached debugger.
+pub struct GdbStub<'e, 'a> {
+    executor: &'e mut Executor<'a>,
+    breakpoints: BTreeSet<u32>,
+}
+
+impl<'e, 'a> GdbStub<'e, 'a> {
+    /// Construct a stub around `executor`, not yet listening.
+    pub fn new(executor: &'e mut Executor<'a>) -> Self {
+        Self {
+            executor,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Bind `addr` and serve a single debugger connection to completion.
+    pub fn serve(&mut self, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        log::info!("gdb stub listening on {addr}");
+        let (stream, peer) = listener.accept()?;
+        log::info!("gdb connected from {peer}");
+        self.handle_connection(stream)
+    }
+
+    fn handle_connection(&mut self, mut stream: TcpStream) -> Result<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = stream.try_clone()?;
+        loop {
+            match read_packet(&mut reader)? {
+                Some(packet) => {
+                    ack(&mut stream)?;
+                    let reply = self.dispatch(&packet)?;
+                    if let Some(reply) = reply {
+                        write_packet(&mut stream, &reply)?;
+                    }
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Handle one decoded RSP packet body, returning the reply payload (if
+    /// any) to send back, unframed.
+    fn dispatch(&mut self, packet: &str) -> Result<Option<String>> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => Some("S05".to_string()),
+            Some(b'g') => Some(self.read_registers()),
+            Some(b'G') => {
+                self.write_registers(&packet[1..])?;
+                Some("OK".to_string())
+            }
+            Some(b'm') => Some(self.read_memory(&packet[1..])?),
+            Some(b'M') => {
+                self.write_memory(&packet[1..])?;
+                Some("OK".to_string())
+            }
+            Some(b'c') => Some(self.cont()?),
+            Some(b's') => Some(self.single_step()?),
+            Some(b'Z') => {
+                self.insert_breakpoint(&packet[1..])?;
+                Some("OK".to_string())
+            }
+            Some(b'z') => {
+                self.remove_breakpoint(&packet[1..])?;
+                Some("OK".to_string())
+            }
+            _ => Some(String::new()), // unsupported: empty reply
+        };
+        Ok(reply)
+    }
+
+    fn read_registers(&self) -> String {
+        let mut out = String::with_capacity(NUM_GDB_REGS * 8);
+        for idx in 0..32 {
+            out.push_str(&le_hex32(self.executor.monitor.load_register(idx)));
+        }
+        out.push_str(&le_hex32(self.executor.pc));
+        out
+    }
+
+    fn write_registers(&mut self, data: &str) -> Result<()> {
+        for (idx, chunk) in data.as_bytes().chunks(8).enumerate() {
+            let value = from_le_hex32(std::str::from_utf8(chunk)?)?;
+            if idx < 32 {
+                self.executor.monitor.store_register(idx, value);
+            } else {
+                self.executor.pc = value;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_memory(&mut self, args: &str) -> Result<String> {
+        let (addr, len) = parse_addr_len(args)?;
+        let mut out = String::with_capacity(len as usize * 2);
+        for offset in 0..len {
+            let addr = addr + offset;
+            let word = self.executor.monitor.load_u32(addr & !0x3);
+            let byte = (word >> ((addr & 0x3) * 8)) as u8;
+            out.push_str(&format!("{byte:02x}"));
+        }
+        Ok(out)
+    }
+
+    fn write_memory(&mut self, args: &str) -> Result<()> {
+        let (header, data) = args
+            .split_once(':')
+            .ok_or_else(|| anyhow!("malformed M packet"))?;
+        let (addr, len) = parse_addr_len(header)?;
+        let bytes = hex_decode(data)?;
+        if bytes.len() as u32 != len {
+            return Err(anyhow!("M packet length mismatch"));
+        }
+        self.executor.monitor.store_region(addr, &bytes);
+        Ok(())
+    }
+
+    /// Run `step()` until a breakpoint's `pc` is hit or the session exits.
+    ///
+    /// `pc` may already sit on a breakpoint when `c` is issued (gdb resuming
+    /// from a previous stop at that same breakpoint), so the current
+    /// instruction is always stepped over once before any breakpoint is
+    /// tested; otherwise `cont` would return immediately without making
+    /// progress and gdb would just resend `c` forever.
+    fn cont(&mut self) -> Result<String> {
+        if let Some(exit_code) = self.executor.step()? {
+            return Ok(stop_reply(exit_code));
+        }
+        loop {
+            if self.breakpoints.contains(&self.executor.pc) {
+                return Ok("S05".to_string());
+            }
+            if let Some(exit_code) = self.executor.step()? {
+                return Ok(stop_reply(exit_code));
+            }
+        }
+    }
+
+    fn single_step(&mut self) -> Result<String> {
+        match self.executor.step()? {
+            Some(exit_code) => Ok(stop_reply(exit_code)),
+            None => Ok("S05".to_string()),
+        }
+    }
+
+    fn insert_breakpoint(&mut self, args: &str) -> Result<()> {
+        let (_kind, rest) = args
+            .split_once(',')
+            .ok_or_else(|| anyhow!("malformed Z packet"))?;
+        let (addr, _kind) = parse_addr_len(rest)?;
+        self.breakpoints.insert(addr);
+        Ok(())
+    }
+
+    fn remove_breakpoint(&mut self, args: &str) -> Result<()> {
+        let (_kind, rest) = args
+            .split_once(',')
+            .ok_or_else(|| anyhow!("malformed z packet"))?;
+        let (addr, _kind) = parse_addr_len(rest)?;
+        self.breakpoints.remove(&addr);
+        Ok(())
+    }
+}
+
+fn stop_reply(exit_code: ExitCode) -> String {
+    match exit_code {
+        ExitCode::Halted(_) => "W00".to_string(),
+        _ => "S05".to_string(),
+    }
+}
+
+fn parse_addr_len(args: &str) -> Result<(u32, u32)> {
+    let (addr, len) = args
+        .split_once(',')
+        .ok_or_else(|| anyhow!("malformed packet: {args}"))?;
+    Ok((
+        u32::from_str_radix(addr, 16)?,
+        u32::from_str_radix(len, 16)?,
+    ))
+}
+
+fn hex_decode(data: &str) -> Result<Vec<u8>> {
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16).map_err(Into::into))
+        .collect()
+}
+
+fn le_hex32(value: u32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn from_le_hex32(hex: &str) -> Result<u32> {
+    let bytes = hex_decode(hex)?;
+    Ok(u32::from_le_bytes(bytes.try_into().map_err(|_| {
+        anyhow!("expected 4 bytes of register data")
+    })?))
+}
+
+/// Checksum of `payload`, per the RSP spec (8-bit sum mod 256).
+fn checksum(payload: &str) -> u8 {
+    payload.bytes().fold(0u8, |acc, byte| acc.wrapping_add(byte))
+}
+
+fn write_packet(stream: &mut TcpStream, payload: &str) -> Result<()> {
+    let framed = format!("${payload}#{:02x}", checksum(payload));
+    stream.write_all(framed.as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn ack(stream: &mut TcpStream) -> Result<()> {
+    stream.write_all(b"+")?;
+    Ok(())
+}
+
+/// Read one `$<payload>#<checksum>` packet, ignoring the leading `+`/`-` ack
+/// bytes sent between packets. Returns `None` on a clean disconnect.
+fn read_packet(stream: &mut TcpStream) -> Result<Option<String>> {
+    let mut byte = [0u8; 1];
+    loop {
+        match stream.read(&mut byte)? {
+            0 => return Ok(None),
+            _ => {}
+        }
+        match byte[0] {
+            b'+' | b'-' => continue,
+            b'$' => break,
+            _ => continue,
+        }
+    }
+
+    let mut payload = Vec::new();
+    loop {
+        if stream.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        if byte[0] == b'#' {
+            break;
+        }
+        payload.push(byte[0]);
+    }
+
+    // Consume the two trailing checksum hex digits.
+    let mut checksum_bytes = [0u8; 2];
+    stream.read_exact(&mut checksum_bytes)?;
+
+    Ok(Some(String::from_utf8(payload)?))
+}
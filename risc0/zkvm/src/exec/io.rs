@@ -0,0 +1,40 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routing of guest-visible file descriptors to host-side [Write] sinks.
+
+use std::{collections::HashMap, io::Write};
+
+/// Routes writes made by the guest to a given file descriptor to a host-side
+/// [Write] implementation.
+#[derive(Default)]
+pub(crate) struct SimpleIo {
+    write_fds: HashMap<u32, Box<dyn Write>>,
+}
+
+impl SimpleIo {
+    /// Register `writer` as the sink for guest writes to `fd`.
+    pub(crate) fn with_write_fd(&mut self, fd: u32, writer: impl Write + 'static) -> &mut Self {
+        self.write_fds.insert(fd, Box::new(writer));
+        self
+    }
+
+    /// Write `bytes` to the sink registered for `fd`, if any.
+    pub(crate) fn write(&mut self, fd: u32, bytes: &[u8]) -> std::io::Result<()> {
+        if let Some(writer) = self.write_fds.get_mut(&fd) {
+            writer.write_all(bytes)?;
+        }
+        Ok(())
+    }
+}
@@ -18,10 +18,13 @@
 //! [Segment]s, each which contains an execution trace of the specified program.
 
 mod env;
+#[cfg(feature = "gdb")]
+pub mod gdb;
 pub(crate) mod io;
 mod monitor;
 #[cfg(feature = "profiler")]
 pub(crate) mod profiler;
+mod snapshot;
 #[cfg(test)]
 mod tests;
 
@@ -41,19 +44,20 @@ use risc0_zkvm_platform::{
     memory::MEM_SIZE,
     syscall::{
         ecall, halt,
-        reg_abi::{REG_A0, REG_A1, REG_A2, REG_A3, REG_A4, REG_T0},
+        reg_abi::{REG_A0, REG_A1, REG_A2, REG_A3, REG_A4, REG_T0, REG_T1},
     },
     PAGE_SIZE, WORD_SIZE,
 };
 use rrs_lib::{instruction_executor::InstructionExecutor, HartState};
 use serde::{Deserialize, Serialize};
 
-pub use self::env::{ExecutorEnv, ExecutorEnvBuilder};
+pub use self::env::{ExecutorEnv, ExecutorEnvBuilder, Interrupt};
+pub use self::snapshot::ExecutorSnapshot;
 use self::monitor::MemoryMonitor;
 use crate::{
     align_up, bonsai_api,
     opcode::{MajorType, OpCode},
-    ExitCode, Loader, MemoryImage, Program, Segment, Session,
+    ExitCode, FaultCause, ImageHandle, Loader, MemoryImage, Program, Segment, Session,
 };
 
 /// The number of cycles required to compress a SHA-256 block.
@@ -64,7 +68,7 @@ const SHA_CYCLES: usize = 72;
 /// The proving phase uses an execution trace generated by the Executor.
 pub struct Executor<'a> {
     env: ExecutorEnv<'a>,
-    pre_image: MemoryImage,
+    pre_image: ImageHandle,
     monitor: MemoryMonitor,
     pre_pc: u32,
     pc: u32,
@@ -75,6 +79,8 @@ pub struct Executor<'a> {
     segments: Vec<Segment>,
     insn_counter: u32,
     bonsai_proof_id: Option<i64>,
+    next_cycle_interrupt: usize,
+    journal: Journal,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -137,11 +143,13 @@ impl<'a> Executor<'a> {
         pc: u32,
         bonsai_proof_id: Option<i64>,
     ) -> Self {
+        let image = ImageHandle::new(image);
         let pre_image = image.clone();
         let monitor = MemoryMonitor::new(image);
         let loader = Loader::new();
         let init_cycles = loader.init_cycles();
         let fini_cycles = loader.fini_cycles();
+        let next_cycle_interrupt = env.cycle_interrupt_interval().unwrap_or(usize::MAX);
 
         Self {
             env,
@@ -156,6 +164,8 @@ impl<'a> Executor<'a> {
             segments: Vec::new(),
             insn_counter: 0,
             bonsai_proof_id: bonsai_proof_id,
+            next_cycle_interrupt,
+            journal: Journal::default(),
         }
     }
 
@@ -206,11 +216,10 @@ impl<'a> Executor<'a> {
 
         self.monitor.clear_session();
 
-        let journal = Journal::default();
         self.env
             .io
             .borrow_mut()
-            .with_write_fd(fileno::JOURNAL, journal.clone());
+            .with_write_fd(fileno::JOURNAL, self.journal.clone());
 
         let mut run_loop = || -> Result<ExitCode> {
             loop {
@@ -219,7 +228,7 @@ impl<'a> Executor<'a> {
                     log::debug!("exit_code: {exit_code:?}, total_cycles: {total_cycles}");
                     assert!(total_cycles <= (1 << self.env.segment_limit_po2));
                     let pre_image = self.pre_image.clone();
-                    self.monitor.image.hash_pages(); // TODO: hash only the dirty pages
+                    self.monitor.image.hash_pages();
                     let post_image_id = self.monitor.image.get_root();
                     let syscalls = take(&mut self.monitor.syscalls);
                     let faults = take(&mut self.monitor.faults);
@@ -248,6 +257,10 @@ impl<'a> Executor<'a> {
                             log::debug!("Halted({inner}): {}", self.segment_cycle);
                             return Ok(exit_code);
                         }
+                        ExitCode::Fault { cause, pc } => {
+                            log::debug!("Fault({cause:?}) at pc 0x{pc:08x}: {}", self.segment_cycle);
+                            return Ok(exit_code);
+                        }
                     };
                 };
             }
@@ -256,7 +269,28 @@ impl<'a> Executor<'a> {
         let exit_code = run_loop()?;
         let mut segments = Vec::new();
         std::mem::swap(&mut segments, &mut self.segments);
-        Ok(Session::new(segments, journal.buf.take(), exit_code))
+        // Only drain the journal on a final exit: [ExitCode::Paused] may be
+        // followed by [Self::snapshot], which needs it still intact.
+        let journal = if matches!(exit_code, ExitCode::Halted(_)) {
+            self.journal.buf.take()
+        } else {
+            self.journal.buf.borrow().clone()
+        };
+        Ok(Session::new(segments, journal, exit_code))
+    }
+
+    /// Run the executor under the control of a GDB debugger attached over
+    /// the address configured with [ExecutorEnvBuilder::gdb], driving
+    /// [Self::step] from the debugger's `continue`/`step` requests instead of
+    /// running to completion unattended.
+    #[cfg(feature = "gdb")]
+    pub fn run_gdb(&mut self) -> Result<()> {
+        let addr = self
+            .env
+            .gdb_listen_addr()
+            .ok_or_else(|| anyhow!("no gdb address configured on this ExecutorEnv"))?;
+        self.monitor.clear_session();
+        gdb::GdbStub::new(self).serve(addr)
     }
 
     fn split(&mut self) {
@@ -276,8 +310,21 @@ impl<'a> Executor<'a> {
             return Ok(Some(ExitCode::SessionLimit));
         }
 
+        if let Some(exit_code) = self.check_cycle_interrupt() {
+            return Ok(Some(exit_code));
+        }
+
+        // `self.pc` may be overwritten by `fault()` below (to redirect to a
+        // trap vector) before we get a chance to trace this instruction;
+        // remember its original value so the trace reports the pc that
+        // actually faulted, not the vector it was redirected to.
+        let insn_pc = self.pc;
+
         let insn = self.monitor.load_u32(self.pc);
-        let opcode = OpCode::decode(insn, self.pc)?;
+        let opcode = match OpCode::decode(insn, self.pc) {
+            Ok(opcode) => opcode,
+            Err(_) => return Ok(self.fault_and_advance(FaultCause::IllegalInstruction)),
+        };
 
         if let Some(op_result) = self.monitor.restore_op() {
             return Ok(self.advance(opcode, op_result));
@@ -293,12 +340,14 @@ impl<'a> Executor<'a> {
                 last_register_write: None,
             };
 
-            InstructionExecutor {
+            if let Err(err) = (InstructionExecutor {
                 mem: &mut self.monitor,
                 hart_state: &mut hart,
             }
-            .step()
-            .map_err(|err| anyhow!("{:?}", err))?;
+            .step())
+            {
+                return Ok(self.fault_and_advance(classify_executor_fault(&err)));
+            }
 
             if let Some(idx) = hart.last_register_write {
                 self.monitor.store_register(idx, hart.registers[idx]);
@@ -311,7 +360,7 @@ impl<'a> Executor<'a> {
         if let Some(ref trace_callback) = self.env.trace_callback {
             trace_callback.borrow_mut()(TraceEvent::InstructionStart {
                 cycle: self.session_cycle() as u32,
-                pc: self.pc,
+                pc: insn_pc,
             })
             .unwrap();
 
@@ -362,6 +411,44 @@ impl<'a> Executor<'a> {
         op_result.exit_code
     }
 
+    /// Handle a fault arising from instruction decode or execution: redirect
+    /// to the guest's registered trap vector if one was configured on the
+    /// [ExecutorEnv], otherwise terminate the session with
+    /// [ExitCode::Fault].
+    fn fault(&mut self, cause: FaultCause) -> Option<ExitCode> {
+        let faulting_pc = self.pc;
+        match self.env.trap_vector() {
+            Some(vector) => {
+                self.monitor.store_register(REG_T0, cause as u32);
+                self.monitor.store_register(REG_T1, faulting_pc);
+                self.pc = vector;
+                None
+            }
+            None => Some(ExitCode::Fault {
+                cause,
+                pc: faulting_pc,
+            }),
+        }
+    }
+
+    /// Like [Self::fault], for call sites (decode and instruction-execution
+    /// faults) that bypass [Self::advance] entirely and so would otherwise
+    /// charge no cycle for the faulting instruction. Without this, a trap
+    /// vector that immediately faults again never advances `session_cycle`,
+    /// so the `SessionLimit` backstop in [Self::step] can never trigger and
+    /// the fault storm loops forever.
+    fn fault_and_advance(&mut self, cause: FaultCause) -> Option<ExitCode> {
+        let exit_code = self.fault(cause);
+        if exit_code.is_none() {
+            self.insn_counter += 1;
+            self.body_cycles += 1;
+            self.segment_cycle =
+                self.init_cycles + self.monitor.total_page_read_cycles() + self.body_cycles;
+            self.monitor.commit(self.session_cycle());
+        }
+        exit_code
+    }
+
     fn total_cycles(&self) -> usize {
         self.init_cycles
             + self.monitor.total_fault_cycles()
@@ -391,13 +478,50 @@ impl<'a> Executor<'a> {
         self.segments.len() * self.env.get_segment_limit() + self.segment_cycle
     }
 
+    /// Fire the [ExecutorEnvBuilder::cycle_callback], if the session cycle
+    /// has crossed the next interval boundary since the last check. Called
+    /// between instruction commits, so it never perturbs the cycle
+    /// accounting `advance` uses for segment splitting.
+    fn check_cycle_interrupt(&mut self) -> Option<ExitCode> {
+        let cycle = self.session_cycle();
+        if cycle < self.next_cycle_interrupt {
+            return None;
+        }
+
+        let interval = self.env.cycle_interrupt_interval()?;
+        // Advance past every interval boundary the session has already
+        // crossed (rather than just one), so a callback that itself takes a
+        // while, or a session that jumps many cycles in one step, doesn't
+        // fire on every subsequent call trying to catch up. `checked_add`
+        // keeps the boundary from wrapping around to a small value (which
+        // would fire on every later call); past the last representable
+        // boundary it stays at `usize::MAX`.
+        while self.next_cycle_interrupt <= cycle {
+            match self.next_cycle_interrupt.checked_add(interval) {
+                Some(next) => self.next_cycle_interrupt = next,
+                None => {
+                    self.next_cycle_interrupt = usize::MAX;
+                    break;
+                }
+            }
+        }
+
+        match self.env.fire_cycle_interrupt() {
+            Interrupt::Continue => None,
+            Interrupt::Stop => Some(ExitCode::Paused),
+        }
+    }
+
     fn ecall(&mut self) -> Result<OpCodeResult> {
         match self.monitor.load_register(REG_T0) {
             ecall::HALT => self.ecall_halt(),
             ecall::OUTPUT => self.ecall_output(),
             ecall::SOFTWARE => self.ecall_software(),
             ecall::SHA => self.ecall_sha(),
-            ecall => bail!("Unknown ecall {ecall:?}"),
+            _ => {
+                let exit_code = self.fault(FaultCause::UnknownEcall);
+                Ok(OpCodeResult::new(self.pc, exit_code, 0, None))
+            }
         }
     }
 
@@ -416,7 +540,10 @@ impl<'a> Executor<'a> {
                 0,
                 None,
             )),
-            _ => bail!("Illegal halt type: {halt_type}"),
+            _ => {
+                let exit_code = self.fault(FaultCause::IllegalHaltType);
+                Ok(OpCodeResult::new(self.pc, exit_code, 0, None))
+            }
         }
     }
 
@@ -513,6 +640,20 @@ impl<'a> Executor<'a> {
     }
 }
 
+/// Classify a [rrs_lib::instruction_executor::InstructionException] into a
+/// [FaultCause]. `rrs_lib` doesn't expose a stable error enum we can
+/// exhaustively match on, so this sniffs its `Debug` output.
+fn classify_executor_fault(err: &dyn Debug) -> FaultCause {
+    let message = format!("{err:?}");
+    if message.contains("Alignment") {
+        FaultCause::MisalignedMemoryAccess
+    } else if message.contains("AccessFault") {
+        FaultCause::OutOfBoundsMemoryAccess
+    } else {
+        FaultCause::IllegalInstruction
+    }
+}
+
 /// An event traced from the running VM.
 #[derive(Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub enum TraceEvent {
@@ -0,0 +1,177 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracks guest memory and register accesses for a single [super::Executor] run,
+//! recording the page faults and syscalls that back a [crate::Segment].
+
+use anyhow::{bail, Result};
+use risc0_zkvm_platform::WORD_SIZE;
+
+use super::{OpCodeResult, SyscallRecord, TraceEvent};
+use crate::ImageHandle;
+
+/// Number of general-purpose registers tracked by the monitor (`x0`..`x31`).
+const NUM_REGISTERS: usize = 32;
+
+/// Number of cycles charged for servicing a single page fault.
+const PAGE_FAULT_CYCLES: usize = 1;
+/// Number of cycles charged for the body of a page-in/page-out.
+const CYCLES_PER_PAGE: usize = 1 << 10;
+
+/// Tracks page faults, syscalls, and register state for the guest memory image
+/// backing a running [super::Executor].
+pub(crate) struct MemoryMonitor {
+    pub(crate) image: ImageHandle,
+    registers: [u32; NUM_REGISTERS],
+    pending_op: Option<OpCodeResult>,
+    pub(crate) syscalls: Vec<SyscallRecord>,
+    pub(crate) faults: Vec<u32>,
+    segment_faults: Vec<u32>,
+    pub(crate) trace_writes: Vec<TraceEvent>,
+}
+
+impl MemoryMonitor {
+    pub(crate) fn new(image: ImageHandle) -> Self {
+        Self {
+            image,
+            registers: [0; NUM_REGISTERS],
+            pending_op: None,
+            syscalls: Vec::new(),
+            faults: Vec::new(),
+            segment_faults: Vec::new(),
+            trace_writes: Vec::new(),
+        }
+    }
+
+    /// A copy of the current register file, for [super::Executor::snapshot].
+    pub(crate) fn registers(&self) -> [u32; NUM_REGISTERS] {
+        self.registers
+    }
+
+    /// Overwrite the register file, for [super::Executor::restore].
+    pub(crate) fn restore_registers(&mut self, registers: [u32; NUM_REGISTERS]) {
+        self.registers = registers;
+    }
+
+    pub(crate) fn clear_session(&mut self) {
+        self.syscalls.clear();
+        self.faults.clear();
+        self.segment_faults.clear();
+    }
+
+    pub(crate) fn clear_segment(&mut self) {
+        self.segment_faults.clear();
+    }
+
+    /// Cache the result of the current instruction, so a retry after a
+    /// mid-instruction segment split can be replayed without re-executing it.
+    pub(crate) fn save_op(&mut self, op_result: OpCodeResult) {
+        self.pending_op = Some(op_result);
+    }
+
+    /// Take the cached op result left by [Self::save_op], if any.
+    pub(crate) fn restore_op(&mut self) -> Option<OpCodeResult> {
+        self.pending_op.take()
+    }
+
+    pub(crate) fn commit(&mut self, _cycle: usize) {
+        self.trace_writes.clear();
+        // A committed instruction is done; only one held back by a split
+        // keeps its cached result for [Self::restore_op].
+        self.pending_op = None;
+    }
+
+    pub(crate) fn load_register(&self, idx: usize) -> u32 {
+        self.registers[idx]
+    }
+
+    pub(crate) fn load_registers<const N: usize>(&self, idxs: [usize; N]) -> [u32; N] {
+        idxs.map(|idx| self.registers[idx])
+    }
+
+    pub(crate) fn store_register(&mut self, idx: usize, value: u32) {
+        self.registers[idx] = value;
+        self.trace_writes.push(TraceEvent::RegisterSet {
+            reg: idx,
+            value,
+        });
+    }
+
+    pub(crate) fn load_u32(&mut self, addr: u32) -> u32 {
+        match self.image.load_word(addr) {
+            Some(word) => word,
+            None => {
+                self.record_fault(addr);
+                self.image.load_word(addr).unwrap_or(0)
+            }
+        }
+    }
+
+    pub(crate) fn load_array<const N: usize>(&mut self, addr: u32) -> [u8; N] {
+        let mut buf = [0u8; N];
+        for (word_idx, chunk) in buf.chunks_mut(WORD_SIZE).enumerate() {
+            let word = self.load_u32(addr + (word_idx * WORD_SIZE) as u32);
+            chunk.copy_from_slice(&word.to_le_bytes()[..chunk.len()]);
+        }
+        buf
+    }
+
+    pub(crate) fn load_string(&mut self, mut addr: u32) -> Result<String> {
+        let mut buf = Vec::new();
+        loop {
+            let word = self.load_u32(addr);
+            for byte in word.to_le_bytes() {
+                if byte == 0 {
+                    return Ok(String::from_utf8(buf)?);
+                }
+                buf.push(byte);
+            }
+            addr += WORD_SIZE as u32;
+            if buf.len() > (1 << 20) {
+                bail!("Guest string exceeded maximum length");
+            }
+        }
+    }
+
+    pub(crate) fn store_region(&mut self, addr: u32, slice: &[u8]) {
+        for (i, chunk) in slice.chunks(WORD_SIZE).enumerate() {
+            let mut word_bytes = [0u8; WORD_SIZE];
+            word_bytes[..chunk.len()].copy_from_slice(chunk);
+            let word = u32::from_le_bytes(word_bytes);
+            let word_addr = addr + (i * WORD_SIZE) as u32;
+            self.image.store_word(word_addr, word);
+            self.trace_writes.push(TraceEvent::MemorySet {
+                addr: word_addr,
+                value: word,
+            });
+        }
+    }
+
+    fn record_fault(&mut self, addr: u32) {
+        self.faults.push(addr);
+        self.segment_faults.push(addr);
+    }
+
+    pub(crate) fn total_page_read_cycles(&self) -> usize {
+        self.segment_faults.len() * (PAGE_FAULT_CYCLES + CYCLES_PER_PAGE)
+    }
+
+    pub(crate) fn total_fault_cycles(&self) -> usize {
+        self.faults.len() * (PAGE_FAULT_CYCLES + CYCLES_PER_PAGE)
+    }
+
+    pub(crate) fn total_pending_fault_cycles(&self) -> usize {
+        self.total_page_read_cycles() + PAGE_FAULT_CYCLES + CYCLES_PER_PAGE
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A sampling profiler that attributes cycles (not wall time) to guest
+//! function symbols.
+//!
+//! Register as a [super::ExecutorEnvBuilder::trace_callback] and feed it
+//! every [super::TraceEvent]; it reconstructs the guest call stack from the
+//! link-register writes already visible in the trace and aggregates cycles
+//! per call path, so the result can be dumped as folded/collapsed stacks for
+//! flamegraph tooling.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use anyhow::Result;
+use gimli::{EndianSlice, LittleEndian};
+
+use super::TraceEvent;
+
+/// The RISC-V ABI register number for `ra`, the link register `jal`/`jalr`
+/// write their return address into.
+const REG_RA: usize = 1;
+
+/// One entry in the guest's symbol table, covering `[addr, addr + size)`.
+struct Symbol {
+    addr: u32,
+    size: u32,
+    name: String,
+}
+
+/// A stack frame the profiler believes is currently active, identified by
+/// the pc `jal`/`jalr` will return to.
+struct Frame {
+    function: String,
+    return_addr: u32,
+}
+
+/// Collect the address of every `end_sequence` row in `unit`'s line number
+/// program, sorted ascending.
+///
+/// A subprogram DIE without a usable `DW_AT_high_pc` (seen from some
+/// compilers on `-O0` debug builds) leaves us no extent to resolve pcs
+/// against; the line program's sequence boundaries are the only other source
+/// of an address range a compiler guarantees, since each sequence covers a
+/// contiguous run of generated code ending exactly where the next function
+/// (or padding) begins.
+fn line_program_sequence_ends(
+    unit: &gimli::Unit<EndianSlice<'_, LittleEndian>>,
+) -> Result<Vec<u32>> {
+    let mut ends = Vec::new();
+    if let Some(program) = unit.line_program.clone() {
+        let mut rows = program.rows();
+        while let Some((_, row)) = rows.next_row()? {
+            if row.end_sequence() {
+                ends.push(row.address() as u32);
+            }
+        }
+    }
+    ends.sort_unstable();
+    Ok(ends)
+}
+
+/// Walk every compilation unit's DWARF debug info for `DW_TAG_subprogram`
+/// entries, yielding a [Symbol] for each one with a resolvable low pc, name,
+/// and extent. Falls back to the unit's line number program to bound a
+/// subprogram whose `DW_AT_high_pc` is missing or unusable.
+fn dwarf_subprograms(
+    binary: &elf::ElfBytes<elf::endian::LittleEndian>,
+) -> Result<Vec<Symbol>> {
+    let load_section = |id: gimli::SectionId| -> Result<Cow<'_, [u8]>, gimli::Error> {
+        Ok(binary
+            .section_header_by_name(id.name())
+            .ok()
+            .flatten()
+            .and_then(|header| binary.section_data(&header).ok())
+            .map(|(data, _)| Cow::Borrowed(data))
+            .unwrap_or(Cow::Borrowed(&[])))
+    };
+    let dwarf = gimli::Dwarf::load(load_section)?;
+    let dwarf = dwarf.borrow(|section| EndianSlice::new(section, LittleEndian));
+
+    let mut symbols = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let sequence_ends = line_program_sequence_ends(&unit)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+
+            let low_pc = entry
+                .attr_value(gimli::DW_AT_low_pc)?
+                .and_then(|value| dwarf.attr_address(&unit, value).ok().flatten());
+            let high_pc = entry.attr_value(gimli::DW_AT_high_pc)?;
+            let name = entry
+                .attr_value(gimli::DW_AT_name)?
+                .and_then(|value| dwarf.attr_string(&unit, value).ok())
+                .map(|s| String::from_utf8_lossy(&s).into_owned());
+
+            if let (Some(low_pc), Some(name)) = (low_pc, name) {
+                let size = match high_pc {
+                    // DW_AT_high_pc as a constant is an offset from low_pc.
+                    Some(gimli::AttributeValue::Udata(offset)) => Some(offset),
+                    // DW_AT_high_pc as an address is the extent's end.
+                    Some(gimli::AttributeValue::Addr(addr)) => Some(addr.saturating_sub(low_pc)),
+                    _ => None,
+                }
+                .or_else(|| {
+                    // No usable DW_AT_high_pc: bound the function by the next
+                    // line-program sequence end after its low pc.
+                    sequence_ends
+                        .iter()
+                        .find(|&&end| end as u64 > low_pc)
+                        .map(|&end| end as u64 - low_pc)
+                });
+                symbols.push(Symbol {
+                    addr: low_pc as u32,
+                    size: size.unwrap_or(1).max(1) as u32,
+                    name,
+                });
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// Attributes guest execution cycles to call stacks, reconstructed from the
+/// trace of a running [super::Executor].
+pub struct Profiler {
+    symbols: Vec<Symbol>,
+    stack: Vec<Frame>,
+    last_pc: Option<u32>,
+    last_cycle: u32,
+    pending_return_addr: Option<u32>,
+    /// Cycles spent with a given call path (root..=leaf, `;`-joined names)
+    /// on top of the stack.
+    path_cycles: HashMap<String, usize>,
+}
+
+impl Profiler {
+    /// Build a [Profiler] that resolves addresses against the symbol table,
+    /// DWARF `DW_TAG_subprogram` debug info, and DWARF line number program
+    /// of `elf`.
+    ///
+    /// The sources are merged: the ELF symbol table is generally complete
+    /// but can miss statics in a partially-stripped binary, while DWARF
+    /// subprogram entries cover anything the compiler emitted debug info
+    /// for; where both cover the same address the symbol table wins. A
+    /// subprogram's extent normally comes from its `DW_AT_high_pc`, but
+    /// where that's missing or unusable the line number program's
+    /// `end_sequence` rows are used instead, since they're the only other
+    /// place a compiler records where one function's generated code ends
+    /// and the next begins. Resolution is still function-granularity —
+    /// individual line rows are not used to distinguish call sites, since
+    /// frames are keyed by function for call-stack reconstruction.
+    pub fn from_elf(elf: &[u8]) -> Result<Self> {
+        let binary = elf::ElfBytes::<elf::endian::LittleEndian>::minimal_parse(elf)?;
+        let mut symbols = Vec::new();
+        if let Some((symtab, strtab)) = binary.symbol_table()? {
+            for sym in symtab.iter() {
+                if sym.st_name == 0 || sym.st_value == 0 {
+                    continue;
+                }
+                if let Ok(name) = strtab.get(sym.st_name as usize) {
+                    symbols.push(Symbol {
+                        addr: sym.st_value as u32,
+                        size: sym.st_size.max(1) as u32,
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+        symbols.extend(dwarf_subprograms(&binary)?);
+        symbols.sort_by_key(|sym| sym.addr);
+        symbols.dedup_by_key(|sym| sym.addr);
+
+        Ok(Self {
+            symbols,
+            stack: Vec::new(),
+            last_pc: None,
+            last_cycle: 0,
+            pending_return_addr: None,
+            path_cycles: HashMap::new(),
+        })
+    }
+
+    /// Feed one [TraceEvent] from the running [super::Executor] into the
+    /// profiler. Intended to be called from a
+    /// [super::ExecutorEnvBuilder::trace_callback].
+    pub fn on_event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::InstructionStart { cycle, pc } => self.on_instruction_start(cycle, pc),
+            TraceEvent::RegisterSet { reg, value } => self.on_register_set(reg, value),
+            TraceEvent::MemorySet { .. } => {}
+        }
+    }
+
+    fn on_instruction_start(&mut self, cycle: u32, pc: u32) {
+        if self.last_pc.is_some() {
+            let cycles = cycle.saturating_sub(self.last_cycle) as usize;
+            self.attribute(cycles);
+        } else {
+            // Seed the stack with the entry symbol (`main`/`_start`, etc.)
+            // so its self cycles, and everything it calls, are attributed
+            // to a real frame instead of the empty root path, which
+            // `folded_stacks` drops and `self_cycles` reports with an empty
+            // name.
+            self.stack.push(Frame {
+                function: self.function_at(pc),
+                return_addr: u32::MAX,
+            });
+        }
+
+        if let Some(top) = self.stack.last() {
+            if pc == top.return_addr {
+                self.stack.pop();
+            }
+        }
+
+        if let Some(return_addr) = self.pending_return_addr.take() {
+            self.stack.push(Frame {
+                function: self.function_at(pc),
+                return_addr,
+            });
+        }
+
+        self.last_pc = Some(pc);
+        self.last_cycle = cycle;
+    }
+
+    fn on_register_set(&mut self, reg: usize, value: u32) {
+        // `jal`/`jalr` write `pc + 4` into `ra` as they jump; remember that
+        // so the *next* InstructionStart can record the call's target.
+        if reg == REG_RA {
+            if let Some(pc) = self.last_pc {
+                if value == pc + 4 {
+                    self.pending_return_addr = Some(value);
+                }
+            }
+        }
+    }
+
+    fn attribute(&mut self, cycles: usize) {
+        if cycles == 0 {
+            return;
+        }
+        let path = self.current_path();
+        *self.path_cycles.entry(path).or_default() += cycles;
+    }
+
+    fn current_path(&self) -> String {
+        self.stack
+            .iter()
+            .map(|frame| frame.function.as_str())
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    fn function_at(&self, pc: u32) -> String {
+        match self
+            .symbols
+            .binary_search_by(|sym| sym.addr.cmp(&pc))
+            .unwrap_or_else(|idx| idx.saturating_sub(1))
+        {
+            idx if self
+                .symbols
+                .get(idx)
+                .is_some_and(|sym| pc >= sym.addr && pc < sym.addr + sym.size) =>
+            {
+                self.symbols[idx].name.clone()
+            }
+            _ => format!("0x{pc:08x}"),
+        }
+    }
+
+    /// Dump every observed call path as a collapsed/folded stack line
+    /// (`func_a;func_b;func_c cycles`), suitable for flamegraph tooling.
+    pub fn folded_stacks(&self) -> String {
+        let mut lines: Vec<_> = self
+            .path_cycles
+            .iter()
+            .filter(|(path, _)| !path.is_empty())
+            .map(|(path, cycles)| format!("{path} {cycles}"))
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+
+    /// A flat table of self-cycles per leaf function, across all call paths
+    /// that end in it, sorted by descending cycle count.
+    pub fn self_cycles(&self) -> Vec<(String, usize)> {
+        let mut totals: HashMap<&str, usize> = HashMap::new();
+        for (path, cycles) in &self.path_cycles {
+            if let Some(leaf) = path.rsplit(';').next() {
+                *totals.entry(leaf).or_default() += cycles;
+            }
+        }
+        let mut table: Vec<_> = totals
+            .into_iter()
+            .map(|(name, cycles)| (name.to_string(), cycles))
+            .collect();
+        table.sort_by(|a, b| b.1.cmp(&a.1));
+        table
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serializable mid-session snapshots, for pausing a running [super::Executor]
+//! in one process and resuming it, bound to a fresh [super::ExecutorEnv], in
+//! another.
+
+use std::{cell::RefCell, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+
+use super::{monitor::MemoryMonitor, Executor, ExecutorEnv, Journal};
+use crate::{ImageHandle, Loader, Segment};
+
+/// The full resumable state of a [super::Executor] at an
+/// [crate::ExitCode::Paused] boundary.
+#[derive(Serialize, Deserialize)]
+pub struct ExecutorSnapshot {
+    pre_image: ImageHandle,
+    image: ImageHandle,
+    registers: [u32; 32],
+    pc: u32,
+    pre_pc: u32,
+    init_cycles: usize,
+    body_cycles: usize,
+    segment_cycle: usize,
+    insn_counter: u32,
+    segments: Vec<Segment>,
+    journal: Vec<u8>,
+}
+
+impl<'a> Executor<'a> {
+    /// Capture the full resumable state of this (paused) [Executor], for
+    /// serialization with [ExecutorSnapshot]'s `serde` impls.
+    ///
+    /// Both `pre_image` (the segment-start image, needed for segment
+    /// accounting) and the live `monitor.image` (reflecting every write made
+    /// so far this segment) are captured; restoring from `pre_image` alone
+    /// would silently drop any mid-segment memory writes. The returned
+    /// snapshot shares its [ImageHandle]s with this executor rather than
+    /// deep-cloning them, so taking repeated snapshots of a session that
+    /// hasn't touched memory since the last one is cheap.
+    pub fn snapshot(&self) -> ExecutorSnapshot {
+        ExecutorSnapshot {
+            pre_image: self.pre_image.clone(),
+            image: self.monitor.image.clone(),
+            registers: self.monitor.registers(),
+            pc: self.pc,
+            pre_pc: self.pre_pc,
+            init_cycles: self.init_cycles,
+            body_cycles: self.body_cycles,
+            segment_cycle: self.segment_cycle,
+            insn_counter: self.insn_counter,
+            segments: self.segments.clone(),
+            journal: self.journal.buf.borrow().clone(),
+        }
+    }
+
+    /// Reconstruct an [Executor] from a previously-captured
+    /// [ExecutorSnapshot], bound to `env` (typically freshly built, since
+    /// the original [ExecutorEnv] cannot itself be serialized).
+    pub fn restore(env: ExecutorEnv<'a>, snapshot: ExecutorSnapshot) -> Self {
+        let mut monitor = MemoryMonitor::new(snapshot.image);
+        monitor.restore_registers(snapshot.registers);
+        let next_cycle_interrupt = env.cycle_interrupt_interval().unwrap_or(usize::MAX);
+
+        Self {
+            env,
+            pre_image: snapshot.pre_image,
+            monitor,
+            pre_pc: snapshot.pre_pc,
+            pc: snapshot.pc,
+            init_cycles: snapshot.init_cycles,
+            fini_cycles: Loader::new().fini_cycles(),
+            body_cycles: snapshot.body_cycles,
+            segment_cycle: snapshot.segment_cycle,
+            segments: snapshot.segments,
+            insn_counter: snapshot.insn_counter,
+            bonsai_proof_id: None,
+            next_cycle_interrupt,
+            journal: Journal {
+                buf: Rc::new(RefCell::new(snapshot.journal)),
+            },
+        }
+    }
+}
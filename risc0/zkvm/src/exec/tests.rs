@@ -0,0 +1,118 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// The `ecall` instruction word.
+const ECALL_INSN: u32 = 0b1110011;
+
+/// A `t0` dispatch code that is none of `ecall::{HALT, OUTPUT, SOFTWARE,
+/// SHA}`. `t0`'s default of `0` is `ecall::HALT`, so tests must set this.
+const UNKNOWN_ECALL: u32 = 0xffff_ffff;
+
+fn single_insn_image(insn: u32) -> MemoryImage {
+    let program = Program {
+        image: BTreeMap::from([(0u32, insn)]),
+        entry: 0,
+    };
+    MemoryImage::new(&program, PAGE_SIZE as u32).unwrap()
+}
+
+#[test]
+fn unknown_ecall_without_trap_vector_reports_fault_exit_code() {
+    let image = single_insn_image(ECALL_INSN);
+    let mut executor = Executor::new(ExecutorEnv::builder().build(), image, 0);
+    executor.monitor.store_register(REG_T0, UNKNOWN_ECALL);
+
+    let exit_code = executor.step().unwrap();
+    assert_eq!(
+        exit_code,
+        Some(ExitCode::Fault {
+            cause: FaultCause::UnknownEcall,
+            pc: 0,
+        })
+    );
+}
+
+#[test]
+fn unknown_ecall_with_trap_vector_redirects_and_sets_cause_registers() {
+    const TRAP_VECTOR: u32 = 0x1000;
+
+    let image = single_insn_image(ECALL_INSN);
+    let env = ExecutorEnv::builder().trap_handler(TRAP_VECTOR).build();
+    let mut executor = Executor::new(env, image, 0);
+    executor.monitor.store_register(REG_T0, UNKNOWN_ECALL);
+
+    let exit_code = executor.step().unwrap();
+    assert_eq!(exit_code, None);
+    assert_eq!(executor.pc, TRAP_VECTOR);
+    assert_eq!(
+        executor.monitor.load_register(REG_T0),
+        FaultCause::UnknownEcall as u32
+    );
+    assert_eq!(executor.monitor.load_register(REG_T1), 0);
+}
+
+fn nop_image(count: u32) -> MemoryImage {
+    const NOP: u32 = 0x0000_0013; // addi x0, x0, 0
+    let program = Program {
+        image: (0..count).map(|i| (i * WORD_SIZE as u32, NOP)).collect(),
+        entry: 0,
+    };
+    MemoryImage::new(&program, PAGE_SIZE as u32).unwrap()
+}
+
+#[test]
+fn snapshot_restore_resumes_identically_to_uninterrupted_execution() {
+    const STEPS: u32 = 6;
+    const SPLIT: u32 = 3;
+    const SCRATCH_ADDR: u32 = 0x2000;
+    const SCRATCH_VALUE: u32 = 0xdead_beef;
+
+    let mut baseline = Executor::new(ExecutorEnv::builder().build(), nop_image(STEPS), 0);
+    baseline
+        .monitor
+        .store_region(SCRATCH_ADDR, &SCRATCH_VALUE.to_le_bytes());
+    for _ in 0..STEPS {
+        baseline.step().unwrap();
+    }
+    baseline.monitor.image.hash_pages();
+
+    let mut resumable = Executor::new(ExecutorEnv::builder().build(), nop_image(STEPS), 0);
+    for _ in 0..SPLIT {
+        resumable.step().unwrap();
+    }
+    // Write mid-segment, after the snapshot's `pre_image` was captured but
+    // before the snapshot itself, so restoring from `pre_image` instead of
+    // the live image would silently lose this write.
+    resumable
+        .monitor
+        .store_region(SCRATCH_ADDR, &SCRATCH_VALUE.to_le_bytes());
+    let snapshot = resumable.snapshot();
+    let mut restored = Executor::restore(ExecutorEnv::builder().build(), snapshot);
+    for _ in SPLIT..STEPS {
+        restored.step().unwrap();
+    }
+    restored.monitor.image.hash_pages();
+
+    assert_eq!(baseline.pc, restored.pc);
+    assert_eq!(baseline.body_cycles, restored.body_cycles);
+    assert_eq!(baseline.insn_counter, restored.insn_counter);
+    assert_eq!(
+        baseline.monitor.image.get_root(),
+        restored.monitor.image.get_root()
+    );
+}
@@ -0,0 +1,285 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A paged guest memory image, with a Merkle root over its pages.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Deref,
+    rc::Rc,
+};
+
+use anyhow::Result;
+use risc0_zkvm_platform::memory::MEM_SIZE;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest as _, Sha256};
+
+use crate::Program;
+
+/// Hash a single page's bytes into a leaf digest.
+fn hash_page(page: &[u8]) -> [u32; 8] {
+    let digest = Sha256::digest(page);
+    let mut out = [0u32; 8];
+    for (word, chunk) in out.iter_mut().zip(digest.chunks(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    out
+}
+
+/// Combine two child node digests into their parent's digest.
+fn hash_pair(left: &[u32; 8], right: &[u32; 8]) -> [u32; 8] {
+    let mut hasher = Sha256::new();
+    for word in left {
+        hasher.update(word.to_le_bytes());
+    }
+    for word in right {
+        hasher.update(word.to_le_bytes());
+    }
+    let digest = hasher.finalize();
+    let mut out = [0u32; 8];
+    for (word, chunk) in out.iter_mut().zip(digest.chunks(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    out
+}
+
+/// Build the node digests of a complete binary tree of `num_pages`
+/// all-zero leaves, 1-indexed with the root at index `1` and leaves at
+/// `[num_pages, 2 * num_pages)`.
+fn zero_tree(num_pages: u32, page_size: u32) -> Vec<[u32; 8]> {
+    let depth = num_pages.trailing_zeros();
+    let mut by_distance_from_leaves = vec![hash_page(&vec![0u8; page_size as usize])];
+    for _ in 0..depth {
+        let prev = *by_distance_from_leaves.last().unwrap();
+        by_distance_from_leaves.push(hash_pair(&prev, &prev));
+    }
+
+    let mut nodes = vec![[0u32; 8]; (2 * num_pages) as usize];
+    for (index, node) in nodes.iter_mut().enumerate().skip(1) {
+        let depth_from_root = (index as u32).ilog2();
+        *node = by_distance_from_leaves[(depth - depth_from_root) as usize];
+    }
+    nodes
+}
+
+/// A guest memory image, stored one sparse page at a time, with a Merkle
+/// tree of page digests maintained incrementally as pages are written.
+///
+/// Pages that have never been written are implicitly all-zero and are not
+/// materialized in [Self::pages], but still contribute their (precomputed)
+/// zero-page digest to [Self::nodes].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MemoryImage {
+    page_size: u32,
+    /// Number of leaves in [Self::nodes]; the next power of two at or above
+    /// the number of pages in the guest's address space, so the tree is
+    /// complete.
+    num_pages: u32,
+    pages: BTreeMap<u32, Vec<u8>>,
+    /// A complete binary tree of page (and intermediate) digests, 1-indexed
+    /// with the root at index `1`, sized `2 * num_pages`.
+    nodes: Vec<[u32; 8]>,
+    /// Pages written since the last call to [Self::hash_pages], whose leaf
+    /// digest (and ancestors, up to the root) are stale.
+    dirty_pages: BTreeSet<u32>,
+}
+
+impl MemoryImage {
+    /// Build a [MemoryImage] from a loaded [Program], with the given page
+    /// size.
+    pub fn new(program: &Program, page_size: u32) -> Result<Self> {
+        let num_pages = (MEM_SIZE as u32 / page_size).next_power_of_two();
+        let mut image = Self {
+            page_size,
+            num_pages,
+            pages: BTreeMap::new(),
+            nodes: zero_tree(num_pages, page_size),
+            dirty_pages: BTreeSet::new(),
+        };
+        for (&addr, word) in program.image.iter() {
+            image.store_word(addr, *word);
+        }
+        image.hash_pages();
+        Ok(image)
+    }
+
+    fn page_index(&self, addr: u32) -> u32 {
+        addr / self.page_size
+    }
+
+    /// Load the 4-byte-aligned word at `addr`, if that page has been
+    /// written; implicitly-zero pages return `None` so callers can charge a
+    /// page-in fault.
+    pub fn load_word(&self, addr: u32) -> Option<u32> {
+        let page = self.pages.get(&self.page_index(addr))?;
+        let offset = (addr % self.page_size) as usize;
+        Some(u32::from_le_bytes(page[offset..offset + 4].try_into().unwrap()))
+    }
+
+    /// Store the 4-byte-aligned word `value` at `addr`, materializing its
+    /// page if this is the first write to it, and marking the page dirty so
+    /// the next [Self::hash_pages] recomputes its path to the root.
+    pub fn store_word(&mut self, addr: u32, value: u32) {
+        let page_size = self.page_size;
+        let page_idx = self.page_index(addr);
+        let page = self
+            .pages
+            .entry(page_idx)
+            .or_insert_with(|| vec![0u8; page_size as usize]);
+        let offset = (addr % page_size) as usize;
+        page[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        self.dirty_pages.insert(page_idx);
+    }
+
+    /// Recompute the Merkle root, rehashing only the pages dirtied since the
+    /// last call (and their ancestors); clean subtrees are left untouched.
+    pub fn hash_pages(&mut self) {
+        let num_pages = self.num_pages;
+        let mut frontier: BTreeSet<u32> = BTreeSet::new();
+        for page_idx in self.dirty_pages.iter().copied() {
+            let leaf = num_pages + page_idx;
+            let bytes = self
+                .pages
+                .get(&page_idx)
+                .map(|page| page.as_slice())
+                .unwrap_or(&[]);
+            self.nodes[leaf as usize] = if bytes.is_empty() {
+                hash_page(&vec![0u8; self.page_size as usize])
+            } else {
+                hash_page(bytes)
+            };
+            if leaf > 1 {
+                frontier.insert(leaf / 2);
+            }
+        }
+        self.dirty_pages.clear();
+
+        while !frontier.is_empty() {
+            let mut next = BTreeSet::new();
+            for index in frontier {
+                let left = self.nodes[(2 * index) as usize];
+                let right = self.nodes[(2 * index + 1) as usize];
+                self.nodes[index as usize] = hash_pair(&left, &right);
+                if index > 1 {
+                    next.insert(index / 2);
+                }
+            }
+            frontier = next;
+        }
+    }
+
+    /// The Merkle root computed by the last call to [Self::hash_pages].
+    pub fn get_root(&self) -> [u32; 8] {
+        self.nodes[1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small, fast, deterministic PRNG (xorshift64), good enough to
+    /// generate reproducible fuzz inputs without pulling in a `rand`
+    /// dependency.
+    fn next_u64(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// Writing a few hundred random words and periodically rehashing should
+    /// land on the same root as rehashing every materialized page from
+    /// scratch at the end.
+    #[test]
+    fn incremental_hash_matches_full_recompute() {
+        let page_size = 1024u32;
+        let num_pages = 16u32;
+        let mut image = MemoryImage {
+            page_size,
+            num_pages,
+            pages: BTreeMap::new(),
+            nodes: zero_tree(num_pages, page_size),
+            dirty_pages: BTreeSet::new(),
+        };
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..500 {
+            let addr = (next_u64(&mut state) as u32 % (num_pages * page_size)) & !0x3;
+            let value = next_u64(&mut state) as u32;
+            image.store_word(addr, value);
+            if next_u64(&mut state) % 8 == 0 {
+                image.hash_pages();
+            }
+        }
+        image.hash_pages();
+        let incremental_root = image.get_root();
+
+        image.dirty_pages = image.pages.keys().copied().collect();
+        image.hash_pages();
+        let full_recompute_root = image.get_root();
+
+        assert_eq!(incremental_root, full_recompute_root);
+    }
+}
+
+/// A copy-on-write handle to a [MemoryImage].
+///
+/// Cloning an [ImageHandle] is cheap (an `Rc` bump) and shares the
+/// underlying image; the first mutation through either clone diverges it
+/// from the others via [Rc::make_mut]. This keeps operations like
+/// [crate::Executor::snapshot], which want an image as of a particular
+/// point in time, from deep-cloning a potentially large image whenever
+/// nothing has changed.
+#[derive(Clone)]
+pub struct ImageHandle(Rc<MemoryImage>);
+
+impl ImageHandle {
+    /// Wrap `image` in a fresh, uniquely-owned [ImageHandle].
+    pub fn new(image: MemoryImage) -> Self {
+        Self(Rc::new(image))
+    }
+
+    /// Store `value` at `addr`, cloning the underlying image first if it's
+    /// shared with another handle.
+    pub fn store_word(&mut self, addr: u32, value: u32) {
+        Rc::make_mut(&mut self.0).store_word(addr, value);
+    }
+
+    /// Recompute the Merkle root, cloning the underlying image first if
+    /// it's shared with another handle.
+    pub fn hash_pages(&mut self) {
+        Rc::make_mut(&mut self.0).hash_pages();
+    }
+}
+
+impl Deref for ImageHandle {
+    type Target = MemoryImage;
+
+    fn deref(&self) -> &MemoryImage {
+        &self.0
+    }
+}
+
+impl Serialize for ImageHandle {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ImageHandle {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(MemoryImage::deserialize(deserializer)?))
+    }
+}
@@ -0,0 +1,33 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The RISC Zero zkVM: executes a RISC-V guest program and produces the
+//! execution trace ([Session]) used to generate a zero-knowledge proof.
+
+mod bonsai_api;
+mod elf;
+pub mod exec;
+mod image;
+pub mod opcode;
+mod session;
+
+pub use elf::{Loader, Program};
+pub use exec::{Executor, ExecutorEnv, ExecutorEnvBuilder, ExecutorSnapshot, Interrupt};
+pub use image::{ImageHandle, MemoryImage};
+pub use session::{ExitCode, FaultCause, Segment, Session};
+
+/// Round `value` up to the nearest multiple of `align`.
+pub(crate) fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) / align * align
+}
@@ -0,0 +1,64 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoding of RISC-V instructions into the major type used for cycle
+//! accounting and dispatch in the execution phase.
+
+use anyhow::{bail, Result};
+
+/// The major category of a decoded instruction, used to determine how many
+/// cycles it costs and how the [super::exec::Executor] should dispatch it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MajorType {
+    /// A normal RV32IM instruction, executed by `rrs_lib`.
+    Normal,
+    /// An `ecall` instruction, dispatched to [super::exec::Executor::ecall].
+    ECall,
+}
+
+/// A decoded instruction, annotated with the cycle cost of executing it.
+#[derive(Clone, Copy, Debug)]
+pub struct OpCode {
+    /// The raw instruction word.
+    pub insn: u32,
+    /// The major type used to dispatch this instruction.
+    pub major: MajorType,
+    /// The number of cycles this instruction costs, before any
+    /// syscall/ecall-specific extra cycles.
+    pub cycles: usize,
+}
+
+const OPCODE_ECALL: u32 = 0b1110011;
+
+impl OpCode {
+    /// Decode the instruction word `insn`, fetched from `pc`, into an
+    /// [OpCode]. Returns an error for instructions this executor does not
+    /// recognize.
+    pub fn decode(insn: u32, pc: u32) -> Result<Self> {
+        let opcode = insn & 0x7f;
+        if insn == 0 {
+            bail!("Illegal instruction 0x0 at pc 0x{pc:08x}");
+        }
+        let major = if opcode == OPCODE_ECALL {
+            MajorType::ECall
+        } else {
+            MajorType::Normal
+        };
+        Ok(Self {
+            insn,
+            major,
+            cycles: 1,
+        })
+    }
+}
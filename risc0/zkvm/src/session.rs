@@ -0,0 +1,149 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The result of the execution phase: a [Session] made up of one or more
+//! [Segment]s, each terminated by an [ExitCode].
+
+use serde::{Deserialize, Serialize};
+
+use crate::{exec::SyscallRecord, ImageHandle};
+
+/// Describes why a [Segment] (and possibly the whole [Session]) stopped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ExitCode {
+    /// The segment ran out of room and must be continued in a new segment.
+    SystemSplit(u32),
+    /// The session cycle limit configured on the [crate::ExecutorEnv] was
+    /// exceeded.
+    SessionLimit,
+    /// The guest requested a pause (`halt::PAUSE`), or a
+    /// [crate::ExecutorEnvBuilder::cycle_callback] returned
+    /// [crate::Interrupt::Stop]; the session can be
+    /// resumed by constructing a new [crate::Executor] starting from this
+    /// segment's post-state.
+    Paused,
+    /// The guest halted normally (`halt::TERMINATE`), with the given user
+    /// exit code.
+    Halted(u32),
+    /// Execution hit a fault the guest did not register a trap handler for
+    /// (see [crate::ExecutorEnvBuilder::trap_handler]), so the session was
+    /// terminated cleanly instead of propagating an error out of
+    /// [crate::Executor::run].
+    Fault {
+        /// What went wrong.
+        cause: FaultCause,
+        /// The program counter of the faulting instruction.
+        pc: u32,
+    },
+}
+
+/// The reason execution faulted, delivered to a registered guest trap
+/// handler in `t0`, or reported on [ExitCode::Fault] if none was registered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum FaultCause {
+    /// `OpCode::decode` could not decode the instruction word.
+    IllegalInstruction = 0,
+    /// `ecall` was invoked with an unrecognized dispatch code in `t0`.
+    UnknownEcall = 1,
+    /// A memory access was not aligned to its natural size.
+    MisalignedMemoryAccess = 2,
+    /// A memory access fell outside the guest's addressable image.
+    OutOfBoundsMemoryAccess = 3,
+    /// `ecall::HALT` was invoked with an unrecognized halt type in `a0`.
+    IllegalHaltType = 4,
+}
+
+/// A single segment of guest execution: the memory and register state
+/// transition from `pre_image` to `post_image_id`, plus the page faults and
+/// syscalls that occurred along the way.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Segment {
+    pub(crate) pre_image: ImageHandle,
+    pub(crate) post_image_id: [u32; 8],
+    pub(crate) pre_pc: u32,
+    pub(crate) faults: Vec<u32>,
+    pub(crate) syscalls: Vec<SyscallRecord>,
+    pub(crate) exit_code: ExitCode,
+    pub(crate) po2: usize,
+    pub(crate) index: u32,
+}
+
+impl Segment {
+    /// Construct a new [Segment] from the state an [crate::Executor]
+    /// accumulated while producing it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pre_image: ImageHandle,
+        post_image_id: [u32; 8],
+        pre_pc: u32,
+        faults: Vec<u32>,
+        syscalls: Vec<SyscallRecord>,
+        exit_code: ExitCode,
+        po2: usize,
+        index: u32,
+    ) -> Self {
+        Self {
+            pre_image,
+            post_image_id,
+            pre_pc,
+            faults,
+            syscalls,
+            exit_code,
+            po2,
+            index,
+        }
+    }
+}
+
+/// The output of running an [crate::Executor] to completion: every [Segment]
+/// produced, the journal the guest wrote, and why the run stopped.
+pub struct Session {
+    /// The segments making up this session, in execution order.
+    pub segments: Vec<Segment>,
+    /// The bytes the guest wrote to the journal file descriptor.
+    pub journal: Vec<u8>,
+    /// Why the session stopped.
+    pub exit_code: ExitCode,
+    /// The Bonsai proof ID for this session, if it was run remotely.
+    pub bonsai_proof_id: Option<i64>,
+}
+
+impl Session {
+    /// Construct a new [Session] from locally-computed segments.
+    pub fn new(segments: Vec<Segment>, journal: Vec<u8>, exit_code: ExitCode) -> Self {
+        Self {
+            segments,
+            journal,
+            exit_code,
+            bonsai_proof_id: None,
+        }
+    }
+
+    /// Construct a [Session] represented by a Bonsai proof ID rather than
+    /// locally-computed segments.
+    pub fn new_with_proof_id(
+        segments: Vec<Segment>,
+        journal: Vec<u8>,
+        exit_code: ExitCode,
+        bonsai_proof_id: Option<i64>,
+    ) -> Self {
+        Self {
+            segments,
+            journal,
+            exit_code,
+            bonsai_proof_id,
+        }
+    }
+}